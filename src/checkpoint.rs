@@ -0,0 +1,149 @@
+//! Compact on-disk checkpoint format.
+//!
+//! `checkpoint.log` used to be a hex CSV line per improvement, re-scanned in full on every
+//! startup. Instead we append fixed-size `[public(32) || secret(64)]` records, each wrapped in
+//! its own LZ4 frame (shards, concatenated the way `rust-shardio` lays out its files) so writes
+//! stay append-only and cheap. On load, frames are streamed into a bounded `MinMaxHeap` so memory
+//! stays flat regardless of how long a run has been going. A tiny uncompressed footer next to the
+//! log mirrors the current best record so a warm start can grab it without touching the shards.
+
+use crate::{public_key_to_u128, MinedKey};
+use anyhow::Context;
+use ed25519_dalek::{ExpandedSecretKey, PublicKey};
+use min_max_heap::MinMaxHeap;
+use std::{
+    fs::File,
+    io::{self, BufReader, Read, Write},
+    path::{Path, PathBuf},
+};
+
+const RECORD_LEN: usize = 32 + 64;
+
+/// Heap entry ordered solely by `public_key_to_u128(.1)`.
+///
+/// `MinedKey` wraps `ed25519_dalek::PublicKey`/`ExpandedSecretKey`, neither of which implements
+/// `Ord`, so it can't sit in a `MinMaxHeap` tuple directly; this newtype carries the comparable
+/// key alongside it and orders by that key alone.
+pub(crate) struct RankedKey(pub(crate) u128, pub(crate) MinedKey);
+
+impl PartialEq for RankedKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.0 == other.0
+    }
+}
+
+impl Eq for RankedKey {}
+
+impl PartialOrd for RankedKey {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for RankedKey {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.0.cmp(&other.0)
+    }
+}
+
+/// Reads a decoder to its own end-of-frame, discarding anything left (there should be nothing
+/// past `RECORD_LEN` content bytes), so its end marker is consumed from the underlying reader
+/// before the next frame is decoded from the same position.
+fn drain_frame(decoder: &mut impl Read) -> io::Result<()> {
+    let mut discard = [0_u8; 64];
+    loop {
+        match decoder.read(&mut discard)? {
+            0 => return Ok(()),
+            _ => continue,
+        }
+    }
+}
+
+fn footer_path(path: impl AsRef<Path>) -> PathBuf {
+    path.as_ref().with_extension("idx")
+}
+
+fn encode_record(keypair: &MinedKey) -> [u8; RECORD_LEN] {
+    let mut record = [0_u8; RECORD_LEN];
+    record[..32].copy_from_slice(keypair.public.as_bytes());
+    record[32..].copy_from_slice(&keypair.secret.to_bytes());
+    record
+}
+
+fn decode_record(record: &[u8; RECORD_LEN]) -> anyhow::Result<MinedKey> {
+    Ok(MinedKey {
+        public: PublicKey::from_bytes(&record[..32])?,
+        secret: ExpandedSecretKey::from_bytes(&record[32..])?,
+    })
+}
+
+/// Opens `path` for appending and streams its shards into a heap bounded to `keep_top` entries,
+/// evicting the current minimum whenever a fresh record would push it over that bound.
+pub(crate) fn load(
+    path: impl AsRef<Path>,
+    keep_top: usize,
+) -> anyhow::Result<(File, MinMaxHeap<RankedKey>)> {
+    let file = File::options()
+        .create(true)
+        .read(true)
+        .append(true)
+        .open(&path)
+        .context("unable to open checkpoint file")?;
+
+    let mut reader = BufReader::new(File::open(&path).context("unable to reopen checkpoint file")?);
+    let mut heap = MinMaxHeap::with_capacity(keep_top.min(4096));
+
+    loop {
+        let mut decoder = lz4_flex::frame::FrameDecoder::new(&mut reader);
+        let mut record = [0_u8; RECORD_LEN];
+
+        match decoder.read_exact(&mut record) {
+            Ok(()) => {
+                // `read_exact` above only pulls the record's content bytes out of the frame;
+                // the LZ4 end marker that follows is still sitting unread in `reader`. Drain
+                // the decoder to its own EOF so that marker is consumed here, not mistaken for
+                // the next record's frame header on the following loop iteration.
+                drain_frame(&mut decoder).context("malformed checkpoint shard")?;
+
+                if let Ok(keypair) = decode_record(&record) {
+                    heap.push(RankedKey(public_key_to_u128(&keypair), keypair));
+                    if heap.len() > keep_top {
+                        heap.pop_min();
+                    }
+                }
+            }
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(e).context("malformed checkpoint shard"),
+        }
+    }
+
+    Ok((file, heap))
+}
+
+/// Appends one record to the checkpoint log as its own LZ4 frame.
+pub(crate) fn append(file: &mut File, keypair: &MinedKey) -> anyhow::Result<()> {
+    let mut encoder = lz4_flex::frame::FrameEncoder::new(Vec::new());
+    encoder
+        .write_all(&encode_record(keypair))
+        .context("unable to compress checkpoint record")?;
+    let frame = encoder
+        .finish()
+        .context("unable to finish checkpoint shard")?;
+
+    file.write_all(&frame)
+        .context("unable to append checkpoint shard")?;
+    file.flush().context("unable to flush checkpoint file")
+}
+
+/// Overwrites the footer next to `path` with the current best record.
+pub(crate) fn write_footer(path: impl AsRef<Path>, keypair: &MinedKey) -> anyhow::Result<()> {
+    std::fs::write(footer_path(path), encode_record(keypair))
+        .context("unable to write checkpoint footer")
+}
+
+/// Reads the footer next to `path`, if any, without touching the (possibly large) shard log.
+pub(crate) fn read_footer(path: impl AsRef<Path>) -> Option<MinedKey> {
+    let bytes = std::fs::read(footer_path(path)).ok()?;
+    let record: [u8; RECORD_LEN] = bytes.try_into().ok()?;
+    decode_record(&record).ok()
+}
@@ -1,27 +1,112 @@
 use anyhow::Context;
-use ed25519_dalek::{Keypair, PublicKey, SecretKey};
+use curve25519_dalek::{
+    constants::{ED25519_BASEPOINT_POINT, ED25519_BASEPOINT_TABLE},
+    scalar::Scalar,
+};
+use ed25519_dalek::{ExpandedSecretKey, PublicKey};
 use indicatif::{ProgressBar, ProgressState, ProgressStyle};
-use clap::Parser;
+use clap::{Parser, Subcommand};
+use rand::RngCore;
+use regex::Regex;
 use std::{
-    fs::File,
-    io::{BufRead, BufReader, Write},
-    path::Path,
-    sync::mpsc::{sync_channel, Receiver, SyncSender},
+    sync::{
+        mpsc::{sync_channel, Receiver, SyncSender},
+        Arc, Mutex,
+    },
     time::Duration,
 };
 
+mod checkpoint;
+mod net;
+mod shutdown;
+mod subcommands;
+
+use shutdown::ShutdownFlag;
+
+/// A public key found by the search, together with the expanded secret key that produced it.
+///
+/// Keys are mined by incrementally walking the curve (see `run_worker`) rather than hashing a
+/// random seed, so the secret half is not a standard ed25519 seed. Instead it is stored as an
+/// RFC 8032 ss 5.1.5 "expanded" secret key: the 32-byte scalar used to derive the public point,
+/// followed by a 32-byte nonce prefix used for signing. `ExpandedSecretKey` already round-trips
+/// through exactly that 64-byte layout, so it doubles as our on-disk format.
+pub(crate) struct MinedKey {
+    pub(crate) public: PublicKey,
+    pub(crate) secret: ExpandedSecretKey,
+}
+
 #[allow(clippy::large_enum_variant)]
-enum WorkerMessage {
-    Largest(Keypair),
+pub(crate) enum WorkerMessage {
+    Largest(MinedKey),
+    Match(MinedKey),
     Progress { iteration_delta: usize },
 }
 
+/// What a worker is searching the keyspace for.
+#[derive(Clone)]
+pub(crate) enum SearchMode {
+    /// Keep whatever keypair has the largest first-16-bytes-as-u128 public key.
+    Largest,
+    /// Keep every keypair whose public key starts with this byte prefix.
+    Prefix(Vec<u8>),
+    /// Keep every keypair whose hex-encoded public key matches this regex.
+    Match(Regex),
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Sign a message with the best keypair found so far
+    Sign {
+        /// Hex-encoded message to sign
+        #[arg(long)]
+        message: String,
+    },
+    /// Verify a signature made by the best keypair found so far
+    Verify {
+        /// Hex-encoded message that was signed
+        #[arg(long)]
+        message: String,
+        /// Hex-encoded signature to check
+        #[arg(long)]
+        sig: String,
+    },
+    /// Print the best public key found so far, in both raw hex and OpenSSH formats
+    Export,
+}
+
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Args {
+   #[command(subcommand)]
+   command: Option<Command>,
+
    /// The number of threads to use
    #[arg(short, long)]
    jobs: Option<usize>,
+
+   /// Hex-encoded public key prefix to search for, e.g. --prefix cafe
+   #[arg(long, conflicts_with_all = ["prefix_b64", "match_regex"])]
+   prefix: Option<String>,
+
+   /// Base64-encoded public key prefix to search for
+   #[arg(long = "prefix-b64", conflicts_with_all = ["prefix", "match_regex"])]
+   prefix_b64: Option<String>,
+
+   /// Regular expression matched against the hex-encoded public key
+   #[arg(long = "match", conflicts_with_all = ["prefix", "prefix_b64"])]
+   match_regex: Option<String>,
+
+   /// Listen on this address for remote workers (started with --connect) to join the search
+   #[arg(long, conflicts_with = "connect")]
+   listen: Option<String>,
+
+   /// Connect to a controller started with --listen and contribute as a remote worker
+   #[arg(long, conflicts_with = "listen")]
+   connect: Option<String>,
+
+   /// How many of the best keypairs to retain across restarts
+   #[arg(long, default_value_t = 1024)]
+   keep_top: usize,
 }
 
 fn main() {
@@ -32,6 +117,21 @@ fn main() {
 
 fn run_main() -> anyhow::Result<()> {
     let args = Args::parse();
+
+    match &args.command {
+        Some(Command::Sign { message }) => return subcommands::sign(message),
+        Some(Command::Verify { message, sig }) => return subcommands::verify(message, sig),
+        Some(Command::Export) => return subcommands::export(),
+        None => {}
+    }
+
+    let mode = parse_search_mode(&args)?;
+    let shutdown = ShutdownFlag::install()?;
+
+    if let Some(addr) = &args.connect {
+        return net::run_remote_worker(addr, mode, shutdown);
+    }
+
     let num_threads = args.jobs.unwrap_or(num_cpus::get());
 
     println!("bruteforcing with {num_threads} thread{}", if num_threads == 1 { "" } else { "s" });
@@ -44,41 +144,85 @@ fn run_main() -> anyhow::Result<()> {
         to_threads.push(to_thread);
 
         let to_controller = to_controller.clone();
+        let mode = mode.clone();
+        let shutdown = shutdown.clone();
 
         std::thread::spawn(move || {
-            run_worker(from_controller, to_controller);
+            run_worker(from_controller, to_controller, mode, shutdown);
         });
         std::thread::sleep(Duration::from_millis(100));
     }
 
-    run_controller(to_threads, from_threads).context("unable to start controller thread")?;
+    let peers: net::PeerList = Arc::new(Mutex::new(Vec::new()));
+    if let Some(addr) = &args.listen {
+        net::listen_for_workers(addr, to_controller.clone(), Arc::clone(&peers), shutdown.clone())
+            .context("unable to start --listen socket")?;
+    }
+
+    // `run_controller` treats `from_threads.recv()` returning `Err` as "every sender has gone
+    // away, time to wind down" -- so the original sender has to be dropped here, not just its
+    // clones handed to workers and the listener above, or a local run would never notice every
+    // worker exiting on shutdown and `recv` would block forever.
+    drop(to_controller);
+
+    run_controller(to_threads, from_threads, peers, args.keep_top)
+        .context("unable to start controller thread")?;
 
     Ok(())
 }
 
+fn parse_search_mode(args: &Args) -> anyhow::Result<SearchMode> {
+    if let Some(prefix_hex) = &args.prefix {
+        let bytes = hex::decode(prefix_hex).context("--prefix must be valid hex")?;
+        Ok(SearchMode::Prefix(bytes))
+    } else if let Some(prefix_b64) = &args.prefix_b64 {
+        let bytes = base64::decode(prefix_b64).context("--prefix-b64 must be valid base64")?;
+        Ok(SearchMode::Prefix(bytes))
+    } else if let Some(pattern) = &args.match_regex {
+        let regex = Regex::new(pattern).context("--match must be a valid regex")?;
+        Ok(SearchMode::Match(regex))
+    } else {
+        Ok(SearchMode::Largest)
+    }
+}
+
 fn run_controller(
     to_threads: Vec<SyncSender<u128>>,
     from_threads: Receiver<WorkerMessage>,
+    peers: net::PeerList,
+    keep_top: usize,
 ) -> anyhow::Result<()> {
+    let start = std::time::Instant::now();
     let spinner = setup_spinner();
 
-    let (mut checkpoint_file, saved_largest_keypair) =
-        checkpoint_with_largest_keypair("checkpoint.log")
-            .context("unable to create checkpoint file")?;
+    let (mut checkpoint_file, mut heap) = checkpoint::load("checkpoint.log", keep_top)
+        .context("unable to load checkpoint file")?;
 
-    let mut largest_keypair =
-        saved_largest_keypair.unwrap_or_else(|| Keypair::generate(&mut rand::thread_rng()));
+    let footer = checkpoint::read_footer("checkpoint.log");
+
+    // Tracks the value most recently durably written, so the shutdown flush below can skip
+    // re-appending a record that's already on disk instead of writing a redundant shard every
+    // time a run stops without having found anything new.
+    let mut checkpoint_value = footer.as_ref().map(public_key_to_u128);
+
+    if heap.is_empty() {
+        let seed = footer.unwrap_or_else(|| random_mined_key(&mut rand::thread_rng()));
+        heap.push(checkpoint::RankedKey(public_key_to_u128(&seed), seed));
+    }
 
-    let mut largest_value = public_key_to_u128(&largest_keypair);
+    let mut largest_value = heap.peek_max().expect("just seeded").0;
     for sender in &to_threads {
-        sender.send(largest_value).unwrap();
+        sender
+            .send(largest_value)
+            .context("a worker thread disconnected before the search began")?;
     }
 
-    let public_pretty = pretty_print_public(&largest_keypair);
-    spinner.set_message(public_pretty);
+    let mut match_count = 0_usize;
 
-    while let Ok(keypair) = from_threads.recv() {
-        match keypair {
+    spinner.set_message(pretty_print_public(&heap.peek_max().expect("just seeded").1));
+
+    while let Ok(message) = from_threads.recv() {
+        match message {
             WorkerMessage::Largest(keypair) => {
                 let value = public_key_to_u128(&keypair);
 
@@ -86,46 +230,151 @@ fn run_controller(
                     largest_value = value;
 
                     for sender in &to_threads {
-                        sender.send(largest_value).unwrap();
+                        sender
+                            .send(largest_value)
+                            .context("a worker thread disconnected unexpectedly")?;
                     }
+                    net::broadcast_new_best(&peers, largest_value);
 
-                    largest_keypair = keypair;
-
-                    writeln!(checkpoint_file, "{}", serialize_keypair(&largest_keypair))
+                    checkpoint::append(&mut checkpoint_file, &keypair)
                         .context("unable to save keypair to checkpoint file")?;
-                    checkpoint_file.flush()?;
+                    checkpoint::write_footer("checkpoint.log", &keypair)
+                        .context("unable to save checkpoint footer")?;
+                    checkpoint_value = Some(value);
 
-                    let printed_keypair = pretty_print_public(&largest_keypair);
+                    let printed_keypair = pretty_print_public(&keypair);
                     spinner.println(format!(
                         "[{}] {}",
                         chrono::Utc::now().format("%Y-%m-%d %H:%M:%S"),
                         &printed_keypair
                     ));
                     spinner.set_message(printed_keypair);
+
+                    heap.push(checkpoint::RankedKey(value, keypair));
+                    if heap.len() > keep_top {
+                        heap.pop_min();
+                    }
                 }
             }
+            WorkerMessage::Match(keypair) => {
+                match_count += 1;
+
+                checkpoint::append(&mut checkpoint_file, &keypair)
+                    .context("unable to save keypair to checkpoint file")?;
+
+                let printed_keypair = pretty_print_public(&keypair);
+                spinner.println(format!(
+                    "[{}] match #{match_count}: {}",
+                    chrono::Utc::now().format("%Y-%m-%d %H:%M:%S"),
+                    &printed_keypair
+                ));
+                spinner.set_message(format!(
+                    "{match_count} hit{} found, latest: {printed_keypair}",
+                    if match_count == 1 { "" } else { "s" }
+                ));
+            }
             WorkerMessage::Progress { iteration_delta } => {
                 spinner.inc(iteration_delta as u64);
             }
         }
     }
 
+    // Every worker thread has broken out of its loop and dropped its `to_controller` sender,
+    // so `recv` above has nothing left to drain. Flush whatever the heap currently considers
+    // best -- it already reflects every message processed above -- before printing a summary,
+    // but only if it isn't already the record `checkpoint_value` was last set to, or every
+    // shutdown without a fresh improvement would re-append the same seed or best keypair.
+    if let Some(checkpoint::RankedKey(final_value, final_best)) = heap.pop_max() {
+        if checkpoint_value != Some(final_value) {
+            checkpoint::append(&mut checkpoint_file, &final_best)
+                .context("unable to save keypair to checkpoint file")?;
+            checkpoint::write_footer("checkpoint.log", &final_best)
+                .context("unable to save checkpoint footer")?;
+        }
+    }
+
+    let elapsed = start.elapsed();
+    let total_keys = spinner.position();
+    spinner.finish_and_clear();
+
+    println!(
+        "stopped after {total_keys} keys in {:.2?} ({:.2} keys/s)",
+        elapsed,
+        total_keys as f64 / elapsed.as_secs_f64().max(f64::EPSILON)
+    );
+
     Ok(())
 }
 
-fn run_worker(from_controller: Receiver<u128>, to_controller: SyncSender<WorkerMessage>) {
+/// How many candidates a worker walks before jumping to a fresh random starting point.
+///
+/// Without reseeding, every thread that started near the same `a0` would retread the same
+/// stretch of the curve; periodically restarting from a new random scalar keeps threads
+/// spread out across the keyspace.
+const RESEED_INTERVAL: usize = 4_000_000;
+
+fn run_worker(
+    from_controller: Receiver<u128>,
+    to_controller: SyncSender<WorkerMessage>,
+    mode: SearchMode,
+    shutdown: ShutdownFlag,
+) {
     let mut rng = rand::thread_rng();
-    let mut largest_value = from_controller.recv().unwrap();
+    let Ok(mut largest_value) = from_controller.recv() else {
+        return;
+    };
+
+    let mut scalar = Scalar::random(&mut rng);
+    let mut point = &ED25519_BASEPOINT_TABLE * &scalar;
+    let mut since_reseed = 0_usize;
 
     let iteration_delta = u16::MAX as usize;
-    loop {
+    while !shutdown.requested() {
         for _ in 0..iteration_delta {
-            let pair = Keypair::generate(&mut rng);
-            let value = public_key_to_u128(&pair);
+            let public_bytes = point.compress().to_bytes();
+
+            let sent = match &mode {
+                SearchMode::Largest => {
+                    let value = u128::from_be_bytes(public_bytes[0..16].try_into().unwrap());
+
+                    if value > largest_value {
+                        let keypair = mined_key(scalar, &public_bytes, &mut rng);
+                        largest_value = value;
+                        to_controller.send(WorkerMessage::Largest(keypair)).is_ok()
+                    } else {
+                        true
+                    }
+                }
+                SearchMode::Prefix(prefix) => {
+                    if public_bytes.starts_with(prefix) {
+                        let keypair = mined_key(scalar, &public_bytes, &mut rng);
+                        to_controller.send(WorkerMessage::Match(keypair)).is_ok()
+                    } else {
+                        true
+                    }
+                }
+                SearchMode::Match(regex) => {
+                    if regex.is_match(&hex::encode(public_bytes)) {
+                        let keypair = mined_key(scalar, &public_bytes, &mut rng);
+                        to_controller.send(WorkerMessage::Match(keypair)).is_ok()
+                    } else {
+                        true
+                    }
+                }
+            };
+
+            if !sent {
+                return;
+            }
+
+            scalar += Scalar::one();
+            point += ED25519_BASEPOINT_POINT;
+            since_reseed += 1;
 
-            if value > largest_value {
-                to_controller.send(WorkerMessage::Largest(pair)).unwrap();
-                largest_value = value;
+            if since_reseed >= RESEED_INTERVAL {
+                scalar = Scalar::random(&mut rng);
+                point = &ED25519_BASEPOINT_TABLE * &scalar;
+                since_reseed = 0;
             }
         }
 
@@ -137,53 +386,42 @@ fn run_worker(from_controller: Receiver<u128>, to_controller: SyncSender<WorkerM
             .send(WorkerMessage::Progress { iteration_delta })
             .is_err()
         {
-            break;
+            return;
         }
     }
 }
 
-fn pretty_print_public(keypair: &Keypair) -> String {
-    hex::encode(keypair.public)
+/// Builds a [`MinedKey`] for the point `scalar * B`, whose compressed encoding is `public_bytes`.
+///
+/// The nonce half of the expanded secret key isn't derivable from the scalar alone (it normally
+/// comes from hashing a seed), so a fresh random nonce is drawn for every reported key.
+pub(crate) fn mined_key(scalar: Scalar, public_bytes: &[u8; 32], rng: &mut impl RngCore) -> MinedKey {
+    let mut nonce = [0_u8; 32];
+    rng.fill_bytes(&mut nonce);
+
+    let mut expanded_bytes = [0_u8; 64];
+    expanded_bytes[..32].copy_from_slice(scalar.as_bytes());
+    expanded_bytes[32..].copy_from_slice(&nonce);
+
+    MinedKey {
+        public: PublicKey::from_bytes(public_bytes).expect("compressed point is a valid public key"),
+        secret: ExpandedSecretKey::from_bytes(&expanded_bytes)
+            .expect("64-byte buffer is a valid expanded secret key"),
+    }
 }
 
-fn serialize_keypair(keypair: &Keypair) -> String {
-    format!(
-        "{},{}",
-        hex::encode(keypair.public.as_bytes()),
-        hex::encode(keypair.secret.as_bytes())
-    )
+fn random_mined_key(rng: &mut impl RngCore) -> MinedKey {
+    let scalar = Scalar::random(rng);
+    let point = &ED25519_BASEPOINT_TABLE * &scalar;
+    mined_key(scalar, &point.compress().to_bytes(), rng)
 }
 
-fn public_key_to_u128(keypair: &Keypair) -> u128 {
-    u128::from_be_bytes(keypair.public.as_bytes()[0..16].try_into().unwrap())
+fn pretty_print_public(keypair: &MinedKey) -> String {
+    hex::encode(keypair.public)
 }
 
-fn checkpoint_with_largest_keypair(
-    path: impl AsRef<Path>,
-) -> anyhow::Result<(File, Option<Keypair>)> {
-    let checkpoint_file = std::fs::File::options()
-        .create(true)
-        .read(true)
-        .append(true)
-        .open(path)
-        .context("unable to open checkpoint file")?;
-
-    let reader = BufReader::new(&checkpoint_file);
-
-    let mut keypairs = Vec::new();
-    for line in reader.lines().flatten() {
-        let (public_hex, secret_hex) = line.split_once(',').context("malformed keypair line")?;
-        let (public_bytes, secret_bytes) = (hex::decode(public_hex)?, hex::decode(secret_hex)?);
-        let (public, secret) = (
-            PublicKey::from_bytes(&public_bytes)?,
-            SecretKey::from_bytes(&secret_bytes)?,
-        );
-
-        keypairs.push(Keypair { public, secret })
-    }
-
-    let starting_key = keypairs.into_iter().max_by_key(public_key_to_u128);
-    Ok((checkpoint_file, starting_key))
+pub(crate) fn public_key_to_u128(keypair: &MinedKey) -> u128 {
+    u128::from_be_bytes(keypair.public.as_bytes()[0..16].try_into().unwrap())
 }
 
 fn setup_spinner() -> ProgressBar {
@@ -0,0 +1,284 @@
+//! Length-prefixed wire protocol for spreading the search across machines.
+//!
+//! Modeled on a simple controller/worker RPC (in the spirit of ARTIQ's session protocol): a
+//! `--listen` controller accepts any number of `--connect` remote workers, and both sides speak
+//! the same handful of frames the local threads already exchange over `mpsc` channels --
+//! `NewBest`, `Candidate`, `Progress` -- just length-prefixed on a `TcpStream` instead.
+
+use crate::{mined_key, MinedKey, SearchMode, ShutdownFlag, WorkerMessage};
+use anyhow::Context;
+use curve25519_dalek::{
+    constants::{ED25519_BASEPOINT_POINT, ED25519_BASEPOINT_TABLE},
+    scalar::Scalar,
+};
+use ed25519_dalek::{ExpandedSecretKey, PublicKey};
+use std::{
+    io::{self, Read, Write},
+    net::{TcpListener, TcpStream},
+    sync::{mpsc::SyncSender, Arc, Mutex},
+};
+
+const TAG_HELLO: u8 = 0;
+const TAG_NEW_BEST: u8 = 1;
+const TAG_CANDIDATE_LARGEST: u8 = 2;
+const TAG_PROGRESS: u8 = 3;
+const TAG_CANDIDATE_MATCH: u8 = 4;
+
+/// Which local comparison path a remote `Candidate` should be folded into, mirroring the two
+/// ways a local `run_worker` can report a hit (`WorkerMessage::Largest` vs `::Match`).
+enum CandidateKind {
+    Largest,
+    Match,
+}
+
+enum Frame {
+    Hello,
+    NewBest { value: u128 },
+    Candidate {
+        public: [u8; 32],
+        secret: [u8; 64],
+        kind: CandidateKind,
+    },
+    Progress { iteration_delta: usize },
+}
+
+fn write_frame(stream: &mut impl Write, frame: &Frame) -> io::Result<()> {
+    let mut payload = Vec::new();
+    match frame {
+        Frame::Hello => payload.push(TAG_HELLO),
+        Frame::NewBest { value } => {
+            payload.push(TAG_NEW_BEST);
+            payload.extend_from_slice(&value.to_be_bytes());
+        }
+        Frame::Candidate { public, secret, kind } => {
+            payload.push(match kind {
+                CandidateKind::Largest => TAG_CANDIDATE_LARGEST,
+                CandidateKind::Match => TAG_CANDIDATE_MATCH,
+            });
+            payload.extend_from_slice(public);
+            payload.extend_from_slice(secret);
+        }
+        Frame::Progress { iteration_delta } => {
+            payload.push(TAG_PROGRESS);
+            payload.extend_from_slice(&(*iteration_delta as u64).to_be_bytes());
+        }
+    }
+
+    stream.write_all(&(payload.len() as u32).to_be_bytes())?;
+    stream.write_all(&payload)?;
+    stream.flush()
+}
+
+/// Slices `payload[range]`, reporting a truncated frame instead of panicking the way a plain
+/// index would if a peer sends a tag without its full fixed-size body.
+fn payload_slice(payload: &[u8], range: std::ops::Range<usize>) -> io::Result<&[u8]> {
+    payload
+        .get(range)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "truncated frame payload"))
+}
+
+fn read_frame(stream: &mut impl Read) -> io::Result<Frame> {
+    let mut len_bytes = [0_u8; 4];
+    stream.read_exact(&mut len_bytes)?;
+
+    let mut payload = vec![0_u8; u32::from_be_bytes(len_bytes) as usize];
+    stream.read_exact(&mut payload)?;
+
+    match payload.first() {
+        Some(&TAG_HELLO) => Ok(Frame::Hello),
+        Some(&TAG_NEW_BEST) => Ok(Frame::NewBest {
+            value: u128::from_be_bytes(payload_slice(&payload, 1..17)?.try_into().unwrap()),
+        }),
+        Some(&TAG_CANDIDATE_LARGEST) => Ok(Frame::Candidate {
+            public: payload_slice(&payload, 1..33)?.try_into().unwrap(),
+            secret: payload_slice(&payload, 33..97)?.try_into().unwrap(),
+            kind: CandidateKind::Largest,
+        }),
+        Some(&TAG_CANDIDATE_MATCH) => Ok(Frame::Candidate {
+            public: payload_slice(&payload, 1..33)?.try_into().unwrap(),
+            secret: payload_slice(&payload, 33..97)?.try_into().unwrap(),
+            kind: CandidateKind::Match,
+        }),
+        Some(&TAG_PROGRESS) => Ok(Frame::Progress {
+            iteration_delta: u64::from_be_bytes(payload_slice(&payload, 1..9)?.try_into().unwrap())
+                as usize,
+        }),
+        _ => Err(io::Error::new(io::ErrorKind::InvalidData, "unknown frame tag")),
+    }
+}
+
+fn frame_to_worker_message(frame: Frame) -> Option<WorkerMessage> {
+    match frame {
+        Frame::Hello | Frame::NewBest { .. } => None,
+        Frame::Candidate { public, secret, kind } => {
+            let keypair = MinedKey {
+                public: PublicKey::from_bytes(&public).ok()?,
+                secret: ExpandedSecretKey::from_bytes(&secret).ok()?,
+            };
+            Some(match kind {
+                CandidateKind::Largest => WorkerMessage::Largest(keypair),
+                CandidateKind::Match => WorkerMessage::Match(keypair),
+            })
+        }
+        Frame::Progress { iteration_delta } => Some(WorkerMessage::Progress { iteration_delta }),
+    }
+}
+
+/// Write halves of every connected remote worker, so the controller can broadcast new bests
+/// the same way it already broadcasts `largest_value` to its local `to_threads`.
+pub(crate) type PeerList = Arc<Mutex<Vec<TcpStream>>>;
+
+/// Accepts remote workers on `addr`, folding their frames into the controller's own channel.
+///
+/// Each accepted connection gets a reader thread that turns `Candidate`/`Progress` frames into
+/// `WorkerMessage`s and feeds them to `to_controller`, exactly as if they had come from a local
+/// `run_worker` thread -- so remote candidates go through the same comparison/checkpoint path
+/// as `WorkerMessage::Largest`. The accept loop itself polls `shutdown` the same way a local
+/// `run_worker` polls it alongside `try_recv`, instead of blocking on `incoming()` forever and
+/// holding its `to_controller` clone past the point everything else has wound down.
+pub(crate) fn listen_for_workers(
+    addr: &str,
+    to_controller: SyncSender<WorkerMessage>,
+    peers: PeerList,
+    shutdown: ShutdownFlag,
+) -> anyhow::Result<()> {
+    let listener = TcpListener::bind(addr).context("unable to bind --listen address")?;
+    listener
+        .set_nonblocking(true)
+        .context("unable to set --listen socket non-blocking")?;
+    println!("listening for remote workers on {addr}");
+
+    std::thread::spawn(move || {
+        while !shutdown.requested() {
+            match listener.accept() {
+                Ok((stream, _)) => {
+                    let to_controller = to_controller.clone();
+
+                    if let Ok(writer) = stream.try_clone() {
+                        peers.lock().unwrap().push(writer);
+                    }
+
+                    std::thread::spawn(move || handle_remote_worker(stream, to_controller));
+                }
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                    std::thread::sleep(std::time::Duration::from_millis(100));
+                }
+                Err(_) => break,
+            }
+        }
+    });
+
+    Ok(())
+}
+
+fn handle_remote_worker(mut stream: TcpStream, to_controller: SyncSender<WorkerMessage>) {
+    if write_frame(&mut stream, &Frame::Hello).is_err() {
+        return;
+    }
+
+    while let Ok(frame) = read_frame(&mut stream) {
+        if let Some(message) = frame_to_worker_message(frame) {
+            if to_controller.send(message).is_err() {
+                break;
+            }
+        }
+    }
+}
+
+/// Broadcasts a new global-best value to every connected remote worker, dropping any peer
+/// whose connection has gone away.
+pub(crate) fn broadcast_new_best(peers: &PeerList, value: u128) {
+    peers
+        .lock()
+        .unwrap()
+        .retain_mut(|peer| write_frame(peer, &Frame::NewBest { value }).is_ok());
+}
+
+/// Connects to a `--listen` controller at `addr` and runs the same incremental search as a
+/// local `run_worker` thread, reporting candidates and progress over the wire instead of
+/// through an `mpsc` channel.
+pub(crate) fn run_remote_worker(
+    addr: &str,
+    mode: SearchMode,
+    shutdown: ShutdownFlag,
+) -> anyhow::Result<()> {
+    let mut stream = TcpStream::connect(addr).context("unable to connect to --connect address")?;
+    println!("connected to controller at {addr}");
+
+    match read_frame(&mut stream).context("controller did not send a handshake")? {
+        Frame::Hello => {}
+        _ => anyhow::bail!("controller did not send a Hello handshake"),
+    }
+    write_frame(&mut stream, &Frame::Hello)?;
+
+    let shared_best = Arc::new(Mutex::new(0_u128));
+    {
+        let shared_best = Arc::clone(&shared_best);
+        let mut updates = stream.try_clone().context("unable to clone socket")?;
+        std::thread::spawn(move || {
+            while let Ok(Frame::NewBest { value }) = read_frame(&mut updates) {
+                let mut best = shared_best.lock().unwrap();
+                if value > *best {
+                    *best = value;
+                }
+            }
+        });
+    }
+
+    let mut rng = rand::thread_rng();
+    let mut largest_value = 0_u128;
+    let mut scalar = Scalar::random(&mut rng);
+    let mut point = &ED25519_BASEPOINT_TABLE * &scalar;
+    let mut since_reseed = 0_usize;
+
+    let iteration_delta = u16::MAX as usize;
+    while !shutdown.requested() {
+        for _ in 0..iteration_delta {
+            let public_bytes = point.compress().to_bytes();
+
+            let is_match = match &mode {
+                SearchMode::Largest => {
+                    u128::from_be_bytes(public_bytes[0..16].try_into().unwrap()) > largest_value
+                }
+                SearchMode::Prefix(prefix) => public_bytes.starts_with(prefix),
+                SearchMode::Match(regex) => regex.is_match(&hex::encode(public_bytes)),
+            };
+
+            if is_match {
+                let keypair = mined_key(scalar, &public_bytes, &mut rng);
+                write_frame(
+                    &mut stream,
+                    &Frame::Candidate {
+                        public: *keypair.public.as_bytes(),
+                        secret: keypair.secret.to_bytes(),
+                        kind: if matches!(mode, SearchMode::Largest) {
+                            CandidateKind::Largest
+                        } else {
+                            CandidateKind::Match
+                        },
+                    },
+                )?;
+
+                if matches!(mode, SearchMode::Largest) {
+                    largest_value =
+                        u128::from_be_bytes(public_bytes[0..16].try_into().unwrap());
+                }
+            }
+
+            scalar += Scalar::one();
+            point += ED25519_BASEPOINT_POINT;
+            since_reseed += 1;
+
+            if since_reseed >= crate::RESEED_INTERVAL {
+                scalar = Scalar::random(&mut rng);
+                point = &ED25519_BASEPOINT_TABLE * &scalar;
+                since_reseed = 0;
+            }
+        }
+
+        write_frame(&mut stream, &Frame::Progress { iteration_delta })?;
+        largest_value = largest_value.max(*shared_best.lock().unwrap());
+    }
+
+    Ok(())
+}
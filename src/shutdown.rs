@@ -0,0 +1,39 @@
+//! Cooperative shutdown signal shared by the controller and every worker.
+//!
+//! A Ctrl-C/SIGTERM sets a single `AtomicBool`; workers poll it next to their `try_recv` calls
+//! and break out of their hot loop instead of being killed mid-flush, dropping their
+//! `to_controller` sender so the controller's `recv` loop winds down and can flush the final
+//! best keypair before returning. `ctrlc` only covers Ctrl-C (SIGINT) by default, so SIGTERM is
+//! wired up separately through `signal_hook`, which flips the same flag directly with no
+//! closure needed.
+
+use anyhow::Context;
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+#[derive(Clone)]
+pub(crate) struct ShutdownFlag(Arc<AtomicBool>);
+
+impl ShutdownFlag {
+    /// Installs a Ctrl-C/SIGTERM handler that flips the returned flag.
+    pub(crate) fn install() -> anyhow::Result<Self> {
+        let flag = Arc::new(AtomicBool::new(false));
+
+        let handler_flag = Arc::clone(&flag);
+        ctrlc::set_handler(move || {
+            handler_flag.store(true, Ordering::SeqCst);
+        })
+        .context("unable to install Ctrl-C handler")?;
+
+        signal_hook::flag::register(signal_hook::consts::SIGTERM, Arc::clone(&flag))
+            .context("unable to install SIGTERM handler")?;
+
+        Ok(Self(flag))
+    }
+
+    pub(crate) fn requested(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+}
@@ -0,0 +1,71 @@
+//! `sign`/`verify`/`export`: prove and use whatever keypair the search has found so far.
+//!
+//! Mirrors the sign/recover split in OpenEthereum's crypto module -- `sign` produces a
+//! signature over caller-supplied bytes, `verify` checks one, and `export` hands the public
+//! half to other tooling (raw hex, or an OpenSSH `authorized_keys` line) so the mined key is
+//! actually usable outside of this crate.
+
+use crate::{checkpoint, checkpoint::RankedKey, MinedKey};
+use anyhow::Context;
+use ed25519_dalek::{PublicKey, Signature, Verifier};
+
+fn best_keypair() -> anyhow::Result<MinedKey> {
+    if let Some(keypair) = checkpoint::read_footer("checkpoint.log") {
+        return Ok(keypair);
+    }
+
+    let (_, mut heap) = checkpoint::load("checkpoint.log", 1)
+        .context("unable to load checkpoint file")?;
+    heap.pop_max()
+        .map(|RankedKey(_, keypair)| keypair)
+        .context("checkpoint.log has no recorded keypair yet")
+}
+
+pub(crate) fn sign(message_hex: &str) -> anyhow::Result<()> {
+    let keypair = best_keypair()?;
+    let message = hex::decode(message_hex).context("--message must be valid hex")?;
+
+    let signature = keypair.secret.sign(&message, &keypair.public);
+    println!("{}", hex::encode(signature.to_bytes()));
+
+    Ok(())
+}
+
+pub(crate) fn verify(message_hex: &str, sig_hex: &str) -> anyhow::Result<()> {
+    let keypair = best_keypair()?;
+    let message = hex::decode(message_hex).context("--message must be valid hex")?;
+    let sig_bytes = hex::decode(sig_hex).context("--sig must be valid hex")?;
+    let signature = Signature::from_bytes(&sig_bytes).context("--sig is not a valid signature")?;
+
+    match keypair.public.verify(&message, &signature) {
+        Ok(()) => {
+            println!("valid");
+            Ok(())
+        }
+        Err(_) => anyhow::bail!("invalid signature"),
+    }
+}
+
+pub(crate) fn export() -> anyhow::Result<()> {
+    let keypair = best_keypair()?;
+
+    println!("public (hex): {}", hex::encode(keypair.public.as_bytes()));
+    println!(
+        "public (authorized_keys): {}",
+        openssh_authorized_key(&keypair.public)
+    );
+
+    Ok(())
+}
+
+fn openssh_authorized_key(public: &PublicKey) -> String {
+    const KEY_TYPE: &[u8] = b"ssh-ed25519";
+
+    let mut blob = Vec::with_capacity(4 + KEY_TYPE.len() + 4 + 32);
+    blob.extend_from_slice(&(KEY_TYPE.len() as u32).to_be_bytes());
+    blob.extend_from_slice(KEY_TYPE);
+    blob.extend_from_slice(&32_u32.to_be_bytes());
+    blob.extend_from_slice(public.as_bytes());
+
+    format!("ssh-ed25519 {} edbrute", base64::encode(blob))
+}